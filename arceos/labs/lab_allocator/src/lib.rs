@@ -1,15 +1,25 @@
 //! Allocator algorithm in lab.
 
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 #![allow(unused_variables)]
 
 use allocator::{BaseAllocator, ByteAllocator, AllocResult, AllocError};
 use core::ptr::NonNull;
 use core::alloc::Layout;
 
-// 内存块表示
+const MIN_ALLOC_SHIFT: usize = 3; // 最小尺寸类：8 字节
+const MAX_ALLOC_SHIFT: usize = 11; // 最大尺寸类：2048 字节，更大的走 large_blocks 兜底
+const MAX_ALLOC_SIZE: usize = 1 << MAX_ALLOC_SHIFT;
+const NUM_CLASSES: usize = MAX_ALLOC_SHIFT - MIN_ALLOC_SHIFT + 1; // 尺寸类的个数
+const SLAB_PAGE_SIZE: usize = 4096; // 尺寸类空闲链表为空时，一次性切出的页大小
+const MAX_LARGE_BLOCKS: usize = 64; // 大块分配很少见，固定数组足够
+
+// 空闲链表的 "空" 哨兵值，0 本身可能是合法的块地址，不能用来表示空
+const NIL: usize = usize::MAX;
+
+// 大块（> MAX_ALLOC_SIZE）内存块，沿用旧版整池块数组的记录方式
 #[derive(Copy, Clone)]
-struct MemoryBlock {
+struct LargeBlock {
     start: usize, // 内存块的起始地址
     size: usize,  // 内存块的大小
     in_use: bool, // 是否正在使用
@@ -17,41 +27,265 @@ struct MemoryBlock {
 
 // 内存分配器实现
 pub struct LabByteAllocator {
-    memory_pool_start: usize,    // 内存池的起始地址
-    memory_pool_size: usize,     // 内存池的总大小
-    blocks: [Option<MemoryBlock>; 1024], // 内存块的管理数组（固定大小）
-    total_used: usize,           // 已用字节数
+    pool_start: usize, // 内存池的起始地址
+    pool_end: usize,   // 内存池的结束地址
+    cursor: usize,     // 尚未划分的内存区域起点，用于切出新的 slab 页/大块
+    // 每个尺寸类的空闲链表表头；空闲块的第一个机器字存放下一个空闲块的地址
+    class_free_list: [Option<usize>; NUM_CLASSES],
+    class_used: [usize; NUM_CLASSES], // 每个尺寸类正在使用的块数
+    large_blocks: [Option<LargeBlock>; MAX_LARGE_BLOCKS], // 大块分配的兜底数组
+    total_used: usize, // 已用字节数
 }
 
 impl LabByteAllocator {
     pub const fn new() -> Self {
         Self {
-            memory_pool_start: 0,
-            memory_pool_size: 0,
-            blocks: [None; 1024],
+            pool_start: 0,
+            pool_end: 0,
+            cursor: 0,
+            class_free_list: [None; NUM_CLASSES],
+            class_used: [0; NUM_CLASSES],
+            large_blocks: [None; MAX_LARGE_BLOCKS],
             total_used: 0,
         }
     }
-}
 
+    // size（已取 size 与 align 的较大值）对应的尺寸类下标，超出范围则走大块兜底
+    fn class_for(size: usize) -> Option<usize> {
+        if size > MAX_ALLOC_SIZE {
+            return None;
+        }
+        let ceil_log2 = usize::BITS - (size.max(1) - 1).leading_zeros();
+        let shift = (ceil_log2 as usize).max(MIN_ALLOC_SHIFT);
+        Some(shift - MIN_ALLOC_SHIFT)
+    }
+
+    // 尺寸类 class 中每个 cell 的字节数
+    fn class_size(class: usize) -> usize {
+        1usize << (MIN_ALLOC_SHIFT + class)
+    }
+
+    unsafe fn read_next(addr: usize) -> usize {
+        *(addr as *const usize)
+    }
+
+    unsafe fn write_next(addr: usize, next: usize) {
+        *(addr as *mut usize) = next;
+    }
+
+    fn push_class(&mut self, class: usize, addr: usize) {
+        let next = self.class_free_list[class].unwrap_or(NIL);
+        unsafe { Self::write_next(addr, next) };
+        self.class_free_list[class] = Some(addr);
+    }
+
+    fn pop_class(&mut self, class: usize) -> Option<usize> {
+        let addr = self.class_free_list[class]?;
+        let next = unsafe { Self::read_next(addr) };
+        self.class_free_list[class] = if next == NIL { None } else { Some(next) };
+        Some(addr)
+    }
+
+    // 从内存池切出一整页，按 class 的 cell 大小切分后挂到该尺寸类的空闲链表
+    fn refill_class(&mut self, class: usize) -> AllocResult {
+        // cursor 可能停在大块分配留下的任意字节处，这里先对齐到页边界再切，
+        // 否则切出的 cell（以及返回的指针）会丢失尺寸类本该保证的对齐
+        let page_start = (self.cursor + SLAB_PAGE_SIZE - 1) & !(SLAB_PAGE_SIZE - 1);
+        if page_start + SLAB_PAGE_SIZE > self.pool_end {
+            return Err(AllocError::NoMemory);
+        }
+        self.cursor = page_start + SLAB_PAGE_SIZE;
+
+        let size = Self::class_size(class);
+        let mut addr = page_start;
+        while addr + size <= page_start + SLAB_PAGE_SIZE {
+            self.push_class(class, addr);
+            addr += size;
+        }
+        Ok(())
+    }
+
+    // 把空闲块插入空位；数组满了就丢弃（和其它地方一样受限于固定数组大小）
+    fn insert_large_free(&mut self, start: usize, size: usize) -> bool {
+        if size == 0 {
+            return true;
+        }
+        for block in self.large_blocks.iter_mut() {
+            if block.is_none() {
+                *block = Some(LargeBlock {
+                    start,
+                    size,
+                    in_use: false,
+                });
+                return true;
+            }
+        }
+        false
+    }
+
+    // 把 idx 处的空闲块与相邻的空闲块合并，直到没有相邻块为止
+    fn coalesce_large(&mut self, idx: usize) {
+        loop {
+            let (start, size) = match self.large_blocks[idx] {
+                Some(b) if !b.in_use => (b.start, b.size),
+                _ => return,
+            };
+
+            let mut merged = false;
+            for j in 0..self.large_blocks.len() {
+                if j == idx {
+                    continue;
+                }
+                if let Some(other) = self.large_blocks[j] {
+                    if other.in_use {
+                        continue;
+                    }
+                    if other.start == start + size {
+                        self.large_blocks[idx] = Some(LargeBlock {
+                            start,
+                            size: size + other.size,
+                            in_use: false,
+                        });
+                        self.large_blocks[j] = None;
+                        merged = true;
+                        break;
+                    } else if other.start + other.size == start {
+                        self.large_blocks[idx] = Some(LargeBlock {
+                            start: other.start,
+                            size: other.size + size,
+                            in_use: false,
+                        });
+                        self.large_blocks[j] = None;
+                        merged = true;
+                        break;
+                    }
+                }
+            }
+            if !merged {
+                return;
+            }
+        }
+    }
+
+    // 为 layout 找到或切出一个大块，同时返回从返回指针起可用的长度；
+    // 指针之前的对齐填充计入 used_bytes，但不计入这个长度
+    fn alloc_large_raw(&mut self, layout: Layout) -> AllocResult<(NonNull<u8>, usize)> {
+        let size = layout.size();
+        let align = layout.align();
+
+        for i in 0..self.large_blocks.len() {
+            if let Some(b) = self.large_blocks[i] {
+                if b.in_use {
+                    continue;
+                }
+                let aligned_start = (b.start + align - 1) & !(align - 1);
+                let end = aligned_start + size;
+                let block_end = b.start + b.size;
+                if end > block_end {
+                    continue;
+                }
+
+                // used_size 包含 b.start 到 aligned_start 之间的对齐填充，
+                // 这样 used_bytes 才不会因为调用方的 layout 而产生偏差
+                let used_size = end - b.start;
+                self.large_blocks[i] = Some(LargeBlock {
+                    start: b.start,
+                    size: used_size,
+                    in_use: true,
+                });
+                self.total_used += used_size;
+
+                // 剩余部分作为新的空闲块保留下来，而不是直接丢弃
+                self.insert_large_free(end, block_end - end);
+
+                let ptr = unsafe { NonNull::new_unchecked(aligned_start as *mut u8) };
+                return Ok((ptr, end - aligned_start));
+            }
+        }
+
+        // 没有空闲块能用，从内存池里切一块新的
+        let start = self.cursor;
+        let aligned_start = (start + align - 1) & !(align - 1);
+        let end = aligned_start + size;
+        if end > self.pool_end {
+            return Err(AllocError::NoMemory);
+        }
+        let used_size = end - start;
+        for block in self.large_blocks.iter_mut() {
+            if block.is_none() {
+                *block = Some(LargeBlock {
+                    start,
+                    size: used_size,
+                    in_use: true,
+                });
+                self.cursor = end;
+                self.total_used += used_size;
+                let ptr = unsafe { NonNull::new_unchecked(aligned_start as *mut u8) };
+                return Ok((ptr, end - aligned_start));
+            }
+        }
+        Err(AllocError::NoMemory)
+    }
+
+    fn dealloc_large(&mut self, pos: NonNull<u8>, layout: Layout) {
+        let addr = pos.as_ptr() as usize;
+        for i in 0..self.large_blocks.len() {
+            if let Some(b) = self.large_blocks[i] {
+                if b.in_use && addr >= b.start && addr < b.start + b.size {
+                    self.total_used -= b.size;
+                    self.large_blocks[i] = Some(LargeBlock {
+                        start: b.start,
+                        size: b.size,
+                        in_use: false,
+                    });
+                    self.coalesce_large(i);
+                    return;
+                }
+            }
+        }
+    }
+
+    // 与 alloc 相同，但返回从指针起实际可用的长度（尺寸类大小，或大块中指针之后的跨度）
+    pub fn alloc_exact(&mut self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        let need = layout.size().max(layout.align());
+
+        match Self::class_for(need) {
+            Some(class) => {
+                if self.class_free_list[class].is_none() {
+                    self.refill_class(class)?;
+                }
+                let addr = self.pop_class(class).ok_or(AllocError::NoMemory)?;
+                self.class_used[class] += 1;
+                let size = Self::class_size(class);
+                self.total_used += size;
+                let ptr = unsafe { NonNull::new_unchecked(addr as *mut u8) };
+                Ok(NonNull::slice_from_raw_parts(ptr, size))
+            }
+            None => {
+                let (ptr, size) = self.alloc_large_raw(layout)?;
+                Ok(NonNull::slice_from_raw_parts(ptr, size))
+            }
+        }
+    }
+}
 
 impl BaseAllocator for LabByteAllocator {
     // 初始化内存池
     fn init(&mut self, start: usize, size: usize) {
-        self.memory_pool_start = start;
-        self.memory_pool_size = size;
-        self.blocks[0] = Some(MemoryBlock {
-            start,
-            size,
-            in_use: false,
-        });
+        self.pool_start = start;
+        self.pool_end = start + size;
+        self.cursor = start;
+        self.class_free_list = [None; NUM_CLASSES];
+        self.class_used = [0; NUM_CLASSES];
+        self.large_blocks = [None; MAX_LARGE_BLOCKS];
+        self.total_used = 0;
     }
 
-    // 添加新的内存区域
+    // 添加新的内存区域，登记为一个空闲的大块
     fn add_memory(&mut self, start: usize, size: usize) -> AllocResult {
-        for block in self.blocks.iter_mut() {
+        for block in self.large_blocks.iter_mut() {
             if block.is_none() {
-                *block = Some(MemoryBlock {
+                *block = Some(LargeBlock {
                     start,
                     size,
                     in_use: false,
@@ -63,58 +297,30 @@ impl BaseAllocator for LabByteAllocator {
     }
 }
 
-
 impl ByteAllocator for LabByteAllocator {
     // 分配内存块
     fn alloc(&mut self, layout: Layout) -> AllocResult<NonNull<u8>> {
-        let size = layout.size();
-        let align = layout.align();
-        let mut new_block = None; // 用于存储新的内存块信息
-
-        for block in self.blocks.iter_mut() {
-            if let Some(b) = block {
-                if !b.in_use && b.size >= size {
-                    // 计算对齐后的起始地址和分配结束地址
-                    let aligned_start = (b.start + align - 1) & !(align - 1);
-                    let end = aligned_start + size;
-
-                    if end <= b.start + b.size {
-                        b.in_use = true;
-                        self.total_used += size;
-
-                        // 如果有剩余空间，记录新块的信息
-                        if end < b.start + b.size {
-                            new_block = Some((end, b.start + b.size - end));
-                        }
-
-                        b.size = size;
-                        return Ok(NonNull::new(aligned_start as *mut u8).unwrap());
-                    }
-                }
-            }
-        }
-        Err(AllocError::NoMemory)
+        self.alloc_exact(layout).map(|s| s.cast())
     }
 
     // 释放内存块
     fn dealloc(&mut self, pos: NonNull<u8>, layout: Layout) {
-        let addr = pos.as_ptr() as usize;
-        let size = layout.size();
+        let need = layout.size().max(layout.align());
 
-        for block in self.blocks.iter_mut() {
-            if let Some(b) = block {
-                if b.start == addr && b.in_use {
-                    b.in_use = false;
-                    self.total_used -= size;
-                    return;
-                }
+        match Self::class_for(need) {
+            Some(class) => {
+                let addr = pos.as_ptr() as usize;
+                self.push_class(class, addr);
+                self.class_used[class] -= 1;
+                self.total_used -= Self::class_size(class);
             }
+            None => self.dealloc_large(pos, layout),
         }
     }
 
     // 总字节数
     fn total_bytes(&self) -> usize {
-        self.memory_pool_size
+        self.pool_end - self.pool_start
     }
 
     // 已用字节数
@@ -124,9 +330,81 @@ impl ByteAllocator for LabByteAllocator {
 
     // 可用字节数
     fn available_bytes(&self) -> usize {
-        self.memory_pool_size - self.total_used
+        self.total_bytes() - self.total_used
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POOL_SIZE: usize = 64 * SLAB_PAGE_SIZE;
+
+    #[repr(align(4096))]
+    #[allow(dead_code)]
+    struct AlignedPool([u8; POOL_SIZE]);
+
+    // 每个测试用例各用一块独立的静态内存，避免并行跑测试时互相踩踏
+    static mut POOL_1: AlignedPool = AlignedPool([0; POOL_SIZE]);
+    static mut POOL_2: AlignedPool = AlignedPool([0; POOL_SIZE]);
+    static mut POOL_3: AlignedPool = AlignedPool([0; POOL_SIZE]);
+    static mut POOL_4: AlignedPool = AlignedPool([0; POOL_SIZE]);
 
+    fn new_allocator(start: usize) -> LabByteAllocator {
+        let mut a = LabByteAllocator::new();
+        a.init(start, POOL_SIZE);
+        a
+    }
+
+    #[test]
+    fn small_alloc_then_dealloc_is_reusable() {
+        let mut a = new_allocator(core::ptr::addr_of!(POOL_1) as usize);
+        let layout = Layout::from_size_align(16, 8).unwrap();
+        let p1 = a.alloc(layout).unwrap();
+        assert_eq!(a.used_bytes(), 16);
+        a.dealloc(p1, layout);
+        assert_eq!(a.used_bytes(), 0);
+
+        // The freed cell should come straight back off the class free
+        // list instead of carving a fresh page.
+        let p2 = a.alloc(layout).unwrap();
+        assert_eq!(p1, p2);
+    }
 
+    #[test]
+    fn large_alloc_coalesces_back() {
+        let mut a = new_allocator(core::ptr::addr_of!(POOL_2) as usize);
+        let layout = Layout::from_size_align(4096, 8).unwrap();
+        let p1 = a.alloc(layout).unwrap();
+        let p2 = a.alloc(layout).unwrap();
+        assert_eq!(a.used_bytes(), 2 * 4096);
+
+        a.dealloc(p1, layout);
+        a.dealloc(p2, layout);
+        assert_eq!(a.used_bytes(), 0);
+
+        // Coalesced back into one free block, so a bigger allocation
+        // should now fit where neither half could have held it alone,
+        // starting at the first half instead of fresh space at the cursor.
+        let big = Layout::from_size_align(8192, 8).unwrap();
+        assert_eq!(a.alloc(big).unwrap(), p1);
+    }
+
+    #[test]
+    fn slab_alloc_stays_aligned_after_large_alloc() {
+        let mut a = new_allocator(core::ptr::addr_of!(POOL_3) as usize);
+        // Bumps `cursor` to an address that isn't page-aligned.
+        a.alloc(Layout::from_size_align(3000, 1).unwrap()).unwrap();
+
+        let p = a.alloc(Layout::from_size_align(8, 128).unwrap()).unwrap();
+        assert_eq!(p.as_ptr() as usize % 128, 0);
+    }
+
+    #[test]
+    fn alloc_exact_reports_the_rounded_up_class_size() {
+        let mut a = new_allocator(core::ptr::addr_of!(POOL_4) as usize);
+        // A 1-byte request still rounds up to the smallest size class (8).
+        let slice = a.alloc_exact(Layout::from_size_align(1, 1).unwrap()).unwrap();
+        assert_eq!(slice.len(), 8);
+    }
+}
@@ -0,0 +1,275 @@
+use allocator::{AllocError, AllocResult, BaseAllocator, PageAllocator};
+
+// 空闲链表的个数，即最大的块是 2^(MAX_ORDER - 1) 个页
+const MAX_ORDER: usize = 32;
+
+// 空闲链表的 "空" 哨兵值，0 本身是合法的页地址，不能用来表示空
+const NIL: usize = usize::MAX;
+
+/// Binary-buddy page allocator: `free_head[i]` is a free list of `2^i`-page
+/// blocks, linked through the first word of each free block itself. Unlike
+/// `EarlyAllocator`, `dealloc_pages` actually merges freed blocks back with
+/// their buddies instead of leaking the pages area.
+pub struct BuddyPageAllocator<const PAGE_SIZE: usize> {
+    start: usize,
+    end: usize,
+    free_head: [Option<usize>; MAX_ORDER],
+    total_pages: usize,
+    used_pages: usize,
+}
+
+impl<const PAGE_SIZE: usize> BuddyPageAllocator<PAGE_SIZE> {
+    pub const fn new() -> Self {
+        Self {
+            start: 0,
+            end: 0,
+            free_head: [None; MAX_ORDER],
+            total_pages: 0,
+            used_pages: 0,
+        }
+    }
+
+    // 读取空闲块头部保存的 next 指针
+    unsafe fn read_next(addr: usize) -> usize {
+        *(addr as *const usize)
+    }
+
+    // 写入空闲块头部的 next 指针
+    unsafe fn write_next(addr: usize, next: usize) {
+        *(addr as *mut usize) = next;
+    }
+
+    // 将 addr 头插到 order 对应的空闲链表
+    fn push_free(&mut self, order: usize, addr: usize) {
+        let next = self.free_head[order].unwrap_or(NIL);
+        unsafe { Self::write_next(addr, next) };
+        self.free_head[order] = Some(addr);
+    }
+
+    // 从 order 对应的空闲链表中摘除 target（如果存在）
+    fn remove_free(&mut self, order: usize, target: usize) -> bool {
+        let mut prev: Option<usize> = None;
+        let mut cur = self.free_head[order];
+        while let Some(addr) = cur {
+            let next = unsafe { Self::read_next(addr) };
+            let next = if next == NIL { None } else { Some(next) };
+            if addr == target {
+                match prev {
+                    Some(p) => unsafe { Self::write_next(p, next.unwrap_or(NIL)) },
+                    None => self.free_head[order] = next,
+                }
+                return true;
+            }
+            prev = Some(addr);
+            cur = next;
+        }
+        false
+    }
+
+    // 找到或拆分出一个满足 num_pages 的块，同时返回其真实页数（2 的幂，可能大于 num_pages）
+    fn alloc_pages_raw(&mut self, num_pages: usize, align_pow2: usize) -> AllocResult<(usize, usize)> {
+        let order = (num_pages.next_power_of_two().trailing_zeros() as usize).min(MAX_ORDER - 1);
+        let align = 1usize << align_pow2;
+
+        for j in order..MAX_ORDER {
+            let mut cur = self.free_head[j];
+            while let Some(addr) = cur {
+                let next = unsafe { Self::read_next(addr) };
+                if addr % align == 0 {
+                    self.remove_free(j, addr);
+
+                    // Split the block down to the requested order, pushing
+                    // each upper buddy onto the next-smaller free list.
+                    let mut cur_order = j;
+                    let block = addr;
+                    while cur_order > order {
+                        cur_order -= 1;
+                        let buddy = block + (1usize << cur_order) * PAGE_SIZE;
+                        self.push_free(cur_order, buddy);
+                    }
+
+                    self.used_pages += 1usize << order;
+                    return Ok((block, 1usize << order));
+                }
+                cur = if next == NIL { None } else { Some(next) };
+            }
+        }
+        Err(AllocError::NoMemory)
+    }
+
+    // 与 alloc_pages 相同，但把实际保留的页数也返回给调用者
+    pub fn alloc_pages_exact(&mut self, num_pages: usize, align_pow2: usize) -> AllocResult<(usize, usize)> {
+        self.alloc_pages_raw(num_pages, align_pow2)
+    }
+}
+
+impl<const PAGE_SIZE: usize> BaseAllocator for BuddyPageAllocator<PAGE_SIZE> {
+    fn init(&mut self, start: usize, size: usize) {
+        self.start = start;
+        self.total_pages = size / PAGE_SIZE;
+        self.end = start + self.total_pages * PAGE_SIZE;
+        self.used_pages = 0;
+        self.free_head = [None; MAX_ORDER];
+
+        // Carve the region into the largest power-of-two blocks that stay
+        // both in-bounds and naturally aligned to their own size. Alignment
+        // has to be judged from the absolute address (the classic "lowbit"
+        // trick), not the offset from `start` - `start` itself is rarely
+        // aligned to anything bigger than a page.
+        let page_shift = PAGE_SIZE.trailing_zeros() as usize;
+        let mut offset = 0usize;
+        let mut remaining = self.total_pages;
+        while remaining > 0 {
+            let order_by_size = usize::BITS as usize - 1 - remaining.leading_zeros() as usize;
+            let addr = self.start + offset * PAGE_SIZE;
+            let order_by_align = if addr == 0 {
+                MAX_ORDER - 1
+            } else {
+                (addr.trailing_zeros() as usize).saturating_sub(page_shift)
+            };
+            let order = order_by_size.min(order_by_align).min(MAX_ORDER - 1);
+            self.push_free(order, addr);
+            let block_pages = 1usize << order;
+            offset += block_pages;
+            remaining -= block_pages;
+        }
+    }
+
+    fn add_memory(&mut self, _start: usize, _size: usize) -> AllocResult {
+        Err(AllocError::NoMemory)
+    }
+}
+
+impl<const PAGE_SIZE: usize> PageAllocator for BuddyPageAllocator<PAGE_SIZE> {
+    const PAGE_SIZE: usize = PAGE_SIZE;
+
+    fn alloc_pages(&mut self, num_pages: usize, align_pow2: usize) -> AllocResult<usize> {
+        self.alloc_pages_raw(num_pages, align_pow2).map(|(addr, _)| addr)
+    }
+
+    fn dealloc_pages(&mut self, pos: usize, num_pages: usize) {
+        let freed_order = (num_pages.next_power_of_two().trailing_zeros() as usize).min(MAX_ORDER - 1);
+
+        let mut order = freed_order;
+        let mut block = pos;
+        while order < MAX_ORDER - 1 {
+            let buddy = block ^ ((1usize << order) * PAGE_SIZE);
+            if self.remove_free(order, buddy) {
+                block = block.min(buddy);
+                order += 1;
+            } else {
+                break;
+            }
+        }
+        self.push_free(order, block);
+        self.used_pages -= 1usize << freed_order;
+    }
+
+    fn total_pages(&self) -> usize {
+        self.total_pages
+    }
+
+    fn used_pages(&self) -> usize {
+        self.used_pages
+    }
+
+    fn available_pages(&self) -> usize {
+        self.total_pages - self.used_pages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_pool::AlignedPool;
+
+    const PAGE_SIZE: usize = 4096;
+    const NUM_PAGES: usize = 64;
+    // 比 NUM_PAGES 多留 16 页的余量：静态数组只保证按页对齐，向 64 KiB
+    // 边界取整后仍需留出 NUM_PAGES 页可用
+    const NUM_PAGES_WITH_SLACK: usize = NUM_PAGES + 16;
+
+    static mut POOL_1: AlignedPool<{ PAGE_SIZE * NUM_PAGES }> =
+        AlignedPool([0; PAGE_SIZE * NUM_PAGES]);
+    // 两倍大小：向整池大小（256 KiB）边界取整后仍有 NUM_PAGES 页可用
+    static mut POOL_2: AlignedPool<{ PAGE_SIZE * NUM_PAGES * 2 }> =
+        AlignedPool([0; PAGE_SIZE * NUM_PAGES * 2]);
+    static mut POOL_3: AlignedPool<{ PAGE_SIZE * NUM_PAGES_WITH_SLACK }> =
+        AlignedPool([0; PAGE_SIZE * NUM_PAGES_WITH_SLACK]);
+    static mut POOL_4: AlignedPool<{ PAGE_SIZE * NUM_PAGES }> =
+        AlignedPool([0; PAGE_SIZE * NUM_PAGES]);
+    static mut POOL_5: AlignedPool<{ PAGE_SIZE * NUM_PAGES_WITH_SLACK }> =
+        AlignedPool([0; PAGE_SIZE * NUM_PAGES_WITH_SLACK]);
+
+    fn new_allocator(start: usize) -> (BuddyPageAllocator<PAGE_SIZE>, usize) {
+        let mut a = BuddyPageAllocator::<PAGE_SIZE>::new();
+        a.init(start, PAGE_SIZE * NUM_PAGES);
+        (a, start)
+    }
+
+    #[test]
+    fn alloc_then_dealloc_frees_pages() {
+        let (mut a, _) = new_allocator(core::ptr::addr_of!(POOL_1) as usize);
+        let addr = a.alloc_pages(4, 0).unwrap();
+        assert_eq!(a.used_pages(), 4);
+        a.dealloc_pages(addr, 4);
+        assert_eq!(a.used_pages(), 0);
+        assert_eq!(a.available_pages(), NUM_PAGES);
+    }
+
+    #[test]
+    fn split_then_coalesce_back_to_one_block() {
+        // A single NUM_PAGES block must be naturally aligned to its own
+        // size, so round the (only page-aligned) static up to that boundary.
+        let raw = core::ptr::addr_of!(POOL_2) as usize;
+        let pool_bytes = PAGE_SIZE * NUM_PAGES;
+        let (mut a, start) = new_allocator((raw + pool_bytes - 1) & !(pool_bytes - 1));
+        let p1 = a.alloc_pages(1, 0).unwrap();
+        let p2 = a.alloc_pages(1, 0).unwrap();
+        assert_eq!(a.used_pages(), 2);
+
+        a.dealloc_pages(p1, 1);
+        a.dealloc_pages(p2, 1);
+        assert_eq!(a.used_pages(), 0);
+
+        // Buddies should have merged all the way back up, so the whole
+        // pool is allocatable as a single block again.
+        let whole = a.alloc_pages(NUM_PAGES, 0).unwrap();
+        assert_eq!(whole, start);
+    }
+
+    #[test]
+    fn alloc_pages_honors_alignment() {
+        // Round the (only page-aligned) static up to a 64 KiB boundary so
+        // `init` actually sees a block that can satisfy 64 KiB alignment.
+        let raw = core::ptr::addr_of!(POOL_3) as usize;
+        let start = (raw + 0xffff) & !0xffff;
+        let (mut a, _) = new_allocator(start);
+        let addr = a.alloc_pages(1, 16).unwrap(); // 64 KiB alignment
+        assert_eq!(addr % (1 << 16), 0);
+    }
+
+    #[test]
+    fn alloc_pages_honors_alignment_with_unaligned_start() {
+        // `start` is only page-aligned, one page below a 64 KiB boundary -
+        // deliberately NOT pre-rounded like `alloc_pages_honors_alignment`
+        // above, so `init`'s block-alignment math has to get this right on
+        // its own instead of the test papering over it.
+        let raw = core::ptr::addr_of!(POOL_5) as usize;
+        let boundary = (raw + PAGE_SIZE + 0xffff) & !0xffff;
+        let start = boundary - PAGE_SIZE;
+        let mut a = BuddyPageAllocator::<PAGE_SIZE>::new();
+        a.init(start, PAGE_SIZE * NUM_PAGES);
+
+        let addr = a.alloc_pages(1, 16).unwrap(); // 64 KiB alignment
+        assert_eq!(addr % (1 << 16), 0);
+    }
+
+    #[test]
+    fn alloc_pages_exact_reports_the_rounded_up_page_count() {
+        let (mut a, _) = new_allocator(core::ptr::addr_of!(POOL_4) as usize);
+        // 3 pages rounds up to the next power of two: 4.
+        let (_, pages) = a.alloc_pages_exact(3, 0).unwrap();
+        assert_eq!(pages, 4);
+    }
+}
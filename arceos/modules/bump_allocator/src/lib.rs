@@ -1,12 +1,44 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 use core::alloc::Layout;
 use core::ptr::NonNull;
 use allocator::{AllocError, AllocResult, BaseAllocator, ByteAllocator, PageAllocator};
 
+mod buddy;
+mod locked;
+#[cfg(test)]
+mod test_pool;
+
+pub use buddy::BuddyPageAllocator;
+pub use locked::LockedHeap;
+
+// EarlyAllocator 最多可同时管理的内存区域个数：init 建立一个，add_memory 再追加几个
+const MAX_SEGMENTS: usize = 4;
+
+// 一段由 EarlyAllocator 管理的双端内存区域，布局同下面的图示
+#[derive(Copy, Clone)]
+struct Segment {
+    start: usize,
+    b_pos: usize,
+    p_pos: usize,
+    end: usize,
+}
+
+impl Segment {
+    const fn new(start: usize, size: usize) -> Self {
+        let end = start + size;
+        Self {
+            start,
+            b_pos: start,
+            p_pos: end,
+            end,
+        }
+    }
+}
+
 /// Early memory allocator
 /// Use it before formal bytes-allocator and pages-allocator can work!
-/// This is a double-end memory range:
+/// Each managed region is a double-end memory range:
 /// - Alloc bytes forward
 /// - Alloc pages backward
 ///
@@ -18,51 +50,73 @@ use allocator::{AllocError, AllocResult, BaseAllocator, ByteAllocator, PageAlloc
 /// When it goes down to ZERO, free bytes-used area.
 /// For pages area, it will never be freed!
 ///
+/// `add_memory` can register further disjoint regions beyond the one from
+/// `init`; byte/page allocation tries each region in turn.
+///
 pub struct EarlyAllocator<const PAGE_SIZE: usize>{
-    start: usize,
-    b_pos: usize,
-    p_pos: usize,
-    end: usize,
-
+    segments: [Option<Segment>; MAX_SEGMENTS],
 }
 
 impl<const PAGE_SIZE: usize> EarlyAllocator<PAGE_SIZE> {
     pub const fn new() -> Self {
         Self {
-            start: 0,
-            b_pos: 0,
-            p_pos: 0,
-            end: 0,
+            segments: [None; MAX_SEGMENTS],
         }
     }
+
+    // 汇报每个区域里尚未分配的 b_pos..p_pos 区间，返回写入 out 的个数
+    pub fn remain_areas(&self, out: &mut [(usize, usize)]) -> usize {
+        let mut n = 0;
+        for seg in self.segments.iter().flatten() {
+            if n >= out.len() {
+                break;
+            }
+            if seg.b_pos < seg.p_pos {
+                out[n] = (seg.b_pos, seg.p_pos);
+                n += 1;
+            }
+        }
+        n
+    }
+
+    // 与 alloc 相同，但把实际保留的长度也返回给调用者
+    pub fn alloc_exact(&mut self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        let align = layout.align();
+        let size = layout.size();
+        for seg in self.segments.iter_mut().flatten() {
+            let alloc_start = (seg.b_pos + align - 1) & !(align - 1);
+            let alloc_end = alloc_start + size;
+            if alloc_end > seg.p_pos {
+                continue;
+            }
+            seg.b_pos = alloc_end;
+            let ptr = unsafe { NonNull::new_unchecked(alloc_start as *mut u8) };
+            return Ok(NonNull::slice_from_raw_parts(ptr, alloc_end - alloc_start));
+        }
+        Err(AllocError::NoMemory)
+    }
 }
 
 impl<const PAGE_SIZE: usize> BaseAllocator for EarlyAllocator<PAGE_SIZE> {
     fn init(&mut self, start: usize, size: usize) {
-        self.start = start;
-        self.end = start + size;
-        self.b_pos = start;
-        self.p_pos = self.end;
+        self.segments = [None; MAX_SEGMENTS];
+        self.segments[0] = Some(Segment::new(start, size));
     }
 
     fn add_memory(&mut self, start: usize, size: usize) -> AllocResult {
+        for seg in self.segments.iter_mut() {
+            if seg.is_none() {
+                *seg = Some(Segment::new(start, size));
+                return Ok(());
+            }
+        }
         Err(AllocError::NoMemory)
     }
 }
 
 impl<const PAGE_SIZE: usize> ByteAllocator for EarlyAllocator<PAGE_SIZE> {
     fn alloc(&mut self, layout: Layout) -> AllocResult<NonNull<u8>> {
-        let align = layout.align();
-        let size = layout.size();
-        // 计算对齐后的起始位置
-        let alloc_start = (self.b_pos + align - 1) & !(align - 1);
-        let alloc_end = alloc_start + size;
-        if alloc_end > self.p_pos {
-            return Err(AllocError::NoMemory);
-        }
-        // 更新 b_ops 的位置
-        self.b_pos = alloc_end;
-        Ok(unsafe { NonNull::new_unchecked(alloc_start as *mut u8) })
+        self.alloc_exact(layout).map(|slice| slice.cast())
     }
 
     fn dealloc(&mut self, pos: NonNull<u8>, layout: Layout) {
@@ -70,15 +124,27 @@ impl<const PAGE_SIZE: usize> ByteAllocator for EarlyAllocator<PAGE_SIZE> {
     }
 
     fn total_bytes(&self) -> usize {
-       self.end - self.start
+        self.segments
+            .iter()
+            .flatten()
+            .map(|seg| seg.end - seg.start)
+            .sum()
     }
 
     fn used_bytes(&self) -> usize {
-        self.b_pos - self.start
+        self.segments
+            .iter()
+            .flatten()
+            .map(|seg| seg.b_pos - seg.start)
+            .sum()
     }
 
     fn available_bytes(&self) -> usize {
-        self.p_pos - self.b_pos
+        self.segments
+            .iter()
+            .flatten()
+            .map(|seg| seg.p_pos - seg.b_pos)
+            .sum()
     }
 }
 
@@ -89,18 +155,21 @@ impl<const PAGE_SIZE: usize> PageAllocator for EarlyAllocator<PAGE_SIZE> {
         let align = 1 << align_pow2;
         let size = num_pages * PAGE_SIZE;
 
-        // 计算对齐后的起始位置
-        let alloc_end = self.p_pos;
-        let alloc_start = (alloc_end - size) & !(align - 1);
+        for seg in self.segments.iter_mut().flatten() {
+            // 计算对齐后的起始位置
+            let alloc_end = seg.p_pos;
+            let alloc_start = (alloc_end - size) & !(align - 1);
 
-        // 检查是否超出可用范围
-        if alloc_start < self.b_pos || alloc_start > alloc_end {
-            return Err(AllocError::NoMemory);
-        }
+            // 检查是否超出可用范围
+            if alloc_start < seg.b_pos || alloc_start > alloc_end {
+                continue;
+            }
 
-        // 更新 p_pos 并返回分配的地址
-        self.p_pos = alloc_start;
-        Ok(alloc_start)
+            // 更新 p_pos 并返回分配的地址
+            seg.p_pos = alloc_start;
+            return Ok(alloc_start);
+        }
+        Err(AllocError::NoMemory)
     }
 
     fn dealloc_pages(&mut self, pos: usize, num_pages: usize) {
@@ -108,14 +177,70 @@ impl<const PAGE_SIZE: usize> PageAllocator for EarlyAllocator<PAGE_SIZE> {
     }
 
     fn total_pages(&self) -> usize {
-        (self.end - self.start) / PAGE_SIZE
+        self.segments
+            .iter()
+            .flatten()
+            .map(|seg| (seg.end - seg.start) / PAGE_SIZE)
+            .sum()
     }
 
     fn used_pages(&self) -> usize {
-        (self.end - self.p_pos) / PAGE_SIZE
+        self.segments
+            .iter()
+            .flatten()
+            .map(|seg| (seg.end - seg.p_pos) / PAGE_SIZE)
+            .sum()
     }
 
     fn available_pages(&self) -> usize {
-        (self.p_pos - self.b_pos) / PAGE_SIZE
+        self.segments
+            .iter()
+            .flatten()
+            .map(|seg| (seg.p_pos - seg.b_pos) / PAGE_SIZE)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_pool::AlignedPool;
+
+    const PAGE_SIZE: usize = 4096;
+    const POOL_SIZE: usize = 16 * PAGE_SIZE;
+
+    static mut POOL_A: AlignedPool<POOL_SIZE> = AlignedPool([0; POOL_SIZE]);
+    static mut POOL_B: AlignedPool<POOL_SIZE> = AlignedPool([0; POOL_SIZE]);
+    static mut POOL_C: AlignedPool<POOL_SIZE> = AlignedPool([0; POOL_SIZE]);
+
+    #[test]
+    fn add_memory_registers_a_second_region() {
+        let mut a = EarlyAllocator::<PAGE_SIZE>::new();
+        let start_a = core::ptr::addr_of!(POOL_A) as usize;
+        let start_b = core::ptr::addr_of!(POOL_B) as usize;
+        a.init(start_a, POOL_SIZE);
+        a.add_memory(start_b, POOL_SIZE).unwrap();
+
+        assert_eq!(a.total_bytes(), 2 * POOL_SIZE);
+
+        // Exhaust the first region's byte area, then confirm byte
+        // allocation falls through to the second one.
+        let layout = Layout::from_size_align(POOL_SIZE, 1).unwrap();
+        a.alloc(layout).unwrap();
+        assert!(a.alloc(Layout::from_size_align(1, 1).unwrap()).is_ok());
+    }
+
+    #[test]
+    fn remain_areas_reports_unallocated_gaps() {
+        let mut a = EarlyAllocator::<PAGE_SIZE>::new();
+        let start = core::ptr::addr_of!(POOL_C) as usize;
+        a.init(start, POOL_SIZE);
+        a.alloc(Layout::from_size_align(8, 8).unwrap()).unwrap();
+        a.alloc_pages(1, 0).unwrap();
+
+        let mut out = [(0usize, 0usize); 4];
+        let n = a.remain_areas(&mut out);
+        assert_eq!(n, 1);
+        assert_eq!(out[0].1 - out[0].0, POOL_SIZE - 8 - PAGE_SIZE);
     }
 }
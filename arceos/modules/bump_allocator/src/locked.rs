@@ -0,0 +1,83 @@
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::{self, NonNull};
+
+use allocator::{BaseAllocator, ByteAllocator};
+use spin::Mutex;
+
+// 用 spin::Mutex 包裹任意 ByteAllocator，使其可以作为 #[global_allocator] 使用
+pub struct LockedHeap<A: BaseAllocator + ByteAllocator>(Mutex<A>);
+
+impl<A: BaseAllocator + ByteAllocator> LockedHeap<A> {
+    pub const fn new(inner: A) -> Self {
+        Self(Mutex::new(inner))
+    }
+
+    // 透传给内部分配器的 init
+    pub fn init(&self, start: usize, size: usize) {
+        self.0.lock().init(start, size);
+    }
+}
+
+unsafe impl<A: BaseAllocator + ByteAllocator> GlobalAlloc for LockedHeap<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.0
+            .lock()
+            .alloc(layout)
+            .map(|ptr| ptr.as_ptr())
+            .unwrap_or(ptr::null_mut())
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if let Some(ptr) = NonNull::new(ptr) {
+            self.0.lock().dealloc(ptr, layout);
+        }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.alloc(layout);
+        if !ptr.is_null() {
+            ptr::write_bytes(ptr, 0, layout.size());
+        }
+        ptr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_pool::AlignedPool;
+    use crate::EarlyAllocator;
+
+    const PAGE_SIZE: usize = 4096;
+    const POOL_SIZE: usize = 4 * PAGE_SIZE;
+
+    static mut POOL_1: AlignedPool<POOL_SIZE> = AlignedPool([0; POOL_SIZE]);
+    static mut POOL_2: AlignedPool<POOL_SIZE> = AlignedPool([0; POOL_SIZE]);
+
+    #[test]
+    fn alloc_zeroed_returns_zeroed_bytes() {
+        let heap: LockedHeap<EarlyAllocator<PAGE_SIZE>> = LockedHeap::new(EarlyAllocator::new());
+        heap.init(core::ptr::addr_of!(POOL_1) as usize, POOL_SIZE);
+
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        let ptr = unsafe { heap.alloc_zeroed(layout) };
+        assert!(!ptr.is_null());
+        let bytes = unsafe { core::slice::from_raw_parts(ptr, layout.size()) };
+        assert!(bytes.iter().all(|&b| b == 0));
+        unsafe { heap.dealloc(ptr, layout) };
+    }
+
+    #[test]
+    fn alloc_returns_null_once_the_pool_is_exhausted() {
+        let heap: LockedHeap<EarlyAllocator<PAGE_SIZE>> = LockedHeap::new(EarlyAllocator::new());
+        heap.init(core::ptr::addr_of!(POOL_2) as usize, POOL_SIZE);
+
+        let layout = Layout::from_size_align(POOL_SIZE, 1).unwrap();
+        assert!(!unsafe { heap.alloc(layout) }.is_null());
+
+        // The pool is exhausted now; a further request must fail quietly
+        // with a null pointer instead of panicking.
+        let ptr = unsafe { heap.alloc(Layout::from_size_align(1, 1).unwrap()) };
+        assert!(ptr.is_null());
+    }
+}
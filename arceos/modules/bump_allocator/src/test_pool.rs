@@ -0,0 +1,4 @@
+// 分配器冒烟测试共用的内存池：每个测试用例各用一块独立的静态内存（不同的
+// `N`/实例），避免并行跑测试时互相踩踏。
+#[repr(align(4096))]
+pub(crate) struct AlignedPool<const N: usize>(pub [u8; N]);